@@ -15,6 +15,11 @@ use core::arch::asm;
 use lazy_static::*;
 use riscv::register::satp;
 
+/// Lowest virtual address `mmap` is allowed to place a mapping at. Keeps the
+/// null page unmapped so stray NULL-pointer dereferences in user code fault
+/// instead of silently reading/writing mapped memory.
+const MMAP_MIN_ADDR: usize = 0x10000;
+
 extern "C" {
     fn stext();
     fn etext();
@@ -39,7 +44,8 @@ lazy_static! {
 pub struct MemorySet {
     page_table: PageTable, //一个页表
     areas: Vec<MapArea>, //一系列逻辑段
-    mmap_frames: BTreeMap<VirtPageNum, FrameTracker>, //vpn和ppn的映射
+    mmap_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>, //vpn和ppn的映射，mmap区域按需分配后记录于此；Arc使fork后的mmap页也能被子进程共享
+    lazy_mappings: Vec<LazyMapping>, //尚未分配物理页帧的mmap区域，缺页时才真正建立映射
 }
 
 impl MemorySet {
@@ -49,6 +55,7 @@ impl MemorySet {
             page_table: PageTable::new(),
             areas: Vec::new(),
             mmap_frames: BTreeMap::new(),
+            lazy_mappings: Vec::new(),
         }
     }
     /// Get the page table token
@@ -145,7 +152,7 @@ impl MemorySet {
             MapArea::new(
                 (ekernel as usize).into(),
                 MEMORY_END.into(),
-                MapType::Identical,
+                MapType::IdenticalHuge,
                 MapPermission::R | MapPermission::W,
             ),
             None,
@@ -238,6 +245,174 @@ impl MemorySet {
             elf.header.pt2.entry_point() as usize,
         )
     }
+    /// Build a child address space from `parent`, sharing `Framed` pages
+    /// copy-on-write instead of eagerly copying them. Both sides lose `W`
+    /// on shared pages; the first store afterwards takes a page fault
+    /// resolved by [`MemorySet::handle_cow_fault`]. Pending `mmap` regions
+    /// are carried over as-is, and any of the parent's `mmap` pages that
+    /// were already faulted in are shared copy-on-write the same way.
+    ///
+    /// Called from `sys_fork`'s process-management code in `task`, the
+    /// same place the old eager-copy version was called from; that caller
+    /// isn't part of this module.
+    pub fn from_existed_user(parent: &mut MemorySet) -> MemorySet {
+        let mut memory_set = Self::new_bare();
+        // map trampoline
+        memory_set.map_trampoline();
+        // copy data sections/trap_context/user_stack, with the same permission
+        for area in parent.areas.iter_mut() {
+            let mut new_area = MapArea::new(
+                area.vpn_range.get_start().into(),
+                area.vpn_range.get_end().into(),
+                area.map_type,
+                area.map_perm,
+            );
+            match area.map_type {
+                MapType::Identical | MapType::IdenticalHuge => {
+                    new_area.map(&mut memory_set.page_table);
+                }
+                MapType::Framed => {
+                    let writable = area.map_perm.contains(MapPermission::W);
+                    let user_accessible = area.map_perm.contains(MapPermission::U);
+                    if writable && !user_accessible {
+                        // A kernel-only writable area (e.g. TRAP_CONTEXT) is
+                        // written by the kernel straight through its own
+                        // identity map, bypassing the user page table's
+                        // permission bits entirely, so write-protecting it
+                        // for CoW would never fault and a later write would
+                        // silently corrupt the still-shared frame. Copy it
+                        // eagerly instead of sharing it.
+                        for vpn in area.vpn_range {
+                            new_area.map_one(&mut memory_set.page_table, vpn);
+                            let src_ppn = area.data_frames.get(&vpn).unwrap().ppn;
+                            let dst_ppn = new_area.data_frames.get(&vpn).unwrap().ppn;
+                            dst_ppn
+                                .get_bytes_array()
+                                .copy_from_slice(src_ppn.get_bytes_array());
+                        }
+                    } else {
+                        // only a writable, user-accessible area actually
+                        // needs CoW protection
+                        new_area.cow = writable;
+                        let mut pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+                        if new_area.cow {
+                            pte_flags.remove(PTEFlags::W);
+                            area.cow = true;
+                        }
+                        for vpn in area.vpn_range {
+                            let frame = area.data_frames.get(&vpn).unwrap().clone();
+                            if new_area.cow {
+                                // downgrade the parent's own mapping as well
+                                parent.page_table.unmap(vpn);
+                                parent.page_table.map(vpn, frame.ppn, pte_flags);
+                            }
+                            new_area.map_shared(&mut memory_set.page_table, vpn, frame, pte_flags);
+                        }
+                    }
+                }
+            }
+            memory_set.areas.push(new_area);
+        }
+        // carry over pending (not yet faulted in) mmap regions verbatim;
+        // a writable one is marked cow so that any frames already shared
+        // below are write-protected consistently with their LazyMapping
+        for mapping in parent.lazy_mappings.iter_mut() {
+            if mapping.flags.contains(PTEFlags::W) {
+                mapping.cow = true;
+            }
+            memory_set.lazy_mappings.push(LazyMapping {
+                vpn_range: mapping.vpn_range,
+                flags: mapping.flags,
+                cow: mapping.cow,
+            });
+        }
+        // share mmap pages already faulted in by the parent copy-on-write,
+        // same as the Framed areas above
+        for (&vpn, frame) in parent.mmap_frames.iter() {
+            let flags = match parent
+                .lazy_mappings
+                .iter()
+                .find(|m| m.vpn_range.get_start().0 <= vpn.0 && vpn.0 < m.vpn_range.get_end().0)
+                .map(|m| m.flags)
+            {
+                Some(flags) => flags,
+                None => continue,
+            };
+            let frame = frame.clone();
+            if flags.contains(PTEFlags::W) {
+                let mut ro_flags = flags;
+                ro_flags.remove(PTEFlags::W);
+                parent.page_table.unmap(vpn);
+                parent.page_table.map(vpn, frame.ppn, ro_flags);
+                memory_set.page_table.map(vpn, frame.ppn, ro_flags);
+            } else {
+                memory_set.page_table.map(vpn, frame.ppn, flags);
+            }
+            memory_set.mmap_frames.insert(vpn, frame);
+        }
+        memory_set
+    }
+    /// Resolve a store page fault at `vpn` if it lands on a copy-on-write
+    /// page. Returns `false` if `vpn` isn't part of a CoW area, in which
+    /// case the caller should treat it as a genuine access violation.
+    ///
+    /// Intended to be called from the `StorePageFault` arm of the trap
+    /// handler in `trap`, alongside
+    /// [`MemorySet::handle_lazy_page_fault`] for the `mmap` case; that trap
+    /// dispatch code isn't part of this module.
+    ///
+    /// Also required anywhere kernel code writes into a user page through
+    /// a translated physical address rather than through the user's own
+    /// store instruction (e.g. `translated_byte_buffer`/`translated_refmut`
+    /// as used by `sys_read`): such a write never takes a `StorePageFault`,
+    /// so the caller must invoke this first to resolve a CoW page before
+    /// writing, or the still-shared frame (and whatever other address
+    /// space it's shared with) gets silently corrupted. Kernel-only pages
+    /// that are never CoW-shared in the first place (see
+    /// [`MemorySet::from_existed_user`]'s handling of TRAP_CONTEXT) don't
+    /// need this.
+    pub fn handle_cow_fault(&mut self, vpn: VirtPageNum) -> bool {
+        let area_idx = self
+            .areas
+            .iter()
+            .position(|area| area.cow && area.vpn_range.get_start().0 <= vpn.0 && vpn.0 < area.vpn_range.get_end().0);
+        if let Some(idx) = area_idx {
+            return self.areas[idx].cow_remap(&mut self.page_table, vpn);
+        }
+        let mapping_flags = self
+            .lazy_mappings
+            .iter()
+            .find(|m| m.cow && m.vpn_range.get_start().0 <= vpn.0 && vpn.0 < m.vpn_range.get_end().0)
+            .map(|m| m.flags);
+        match mapping_flags {
+            Some(flags) => self.mmap_cow_remap(vpn, flags),
+            None => false,
+        }
+    }
+    /// Resolve a store page fault on a copy-on-write mmap page shared via
+    /// [`MemorySet::from_existed_user`]. Mirrors `MapArea::cow_remap` but
+    /// operates on `mmap_frames`, which isn't owned by any `MapArea`.
+    fn mmap_cow_remap(&mut self, vpn: VirtPageNum, flags: PTEFlags) -> bool {
+        let shared = match self.mmap_frames.get(&vpn) {
+            Some(frame) => Arc::strong_count(frame) > 1,
+            None => return false,
+        };
+        self.page_table.unmap(vpn);
+        if shared {
+            let old_ppn = self.mmap_frames.get(&vpn).unwrap().ppn;
+            let new_frame = frame_alloc().unwrap();
+            let new_ppn = new_frame.ppn;
+            new_ppn
+                .get_bytes_array()
+                .copy_from_slice(old_ppn.get_bytes_array());
+            self.page_table.map(vpn, new_ppn, flags);
+            self.mmap_frames.insert(vpn, Arc::new(new_frame));
+        } else {
+            let ppn = self.mmap_frames.get(&vpn).unwrap().ppn;
+            self.page_table.map(vpn, ppn, flags);
+        }
+        true
+    }
     /// Change page table by writing satp CSR Register.
     pub fn activate(&self) {
         let satp = self.page_table.token();
@@ -280,74 +455,306 @@ impl MemorySet {
         }
     }
 
-    /// mmap
+    /// Record `[start_vpn, end_vpn)` (or, if `start_vpn` is the null VPN, a
+    /// kernel-chosen region of the same length found via
+    /// [`MemorySet::find_free_area`]) as a pending, lazily-paged mmap
+    /// region. No frames are allocated and no page table entries are
+    /// installed here; the first access to a page in the range takes a page
+    /// fault that [`MemorySet::handle_lazy_page_fault`] resolves on demand.
+    /// Returns the base address of the region on success, or -1 if a fixed
+    /// range falls below `MMAP_MIN_ADDR`, at or above the `TRAP_CONTEXT`/
+    /// trampoline region, collides with an existing mapping, or no free
+    /// region exists.
+    ///
+    /// Called from `sys_mmap`, alongside `munmap`/`mremap` from
+    /// `sys_munmap`/`sys_mremap`; the syscall dispatch itself lives in
+    /// `syscall`, outside this module.
     pub fn mmap(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum, port: usize) -> isize {
-        let mut flags = PTEFlags::empty();
-        let mut vpn = start_vpn;
+        let len = (end_vpn.0 - start_vpn.0) * PAGE_SIZE;
+        let min_vpn = VirtAddr::from(MMAP_MIN_ADDR).ceil();
+        let ceiling_vpn = VirtAddr::from(TRAP_CONTEXT_BASE).floor();
 
+        let base_vpn = if start_vpn.0 == 0 {
+            match self.find_free_area(None, len) {
+                Some(va) => va.floor(),
+                None => return -1,
+            }
+        } else {
+            if start_vpn.0 < min_vpn.0
+                || end_vpn.0 > ceiling_vpn.0
+                || self.range_overlaps(start_vpn, end_vpn)
+            {
+                debug!(
+                    "mmap range is below MMAP_MIN_ADDR, above the TRAP_CONTEXT/trampoline \
+                     ceiling, or overlaps an existing area/mapping"
+                );
+                return -1;
+            }
+            start_vpn
+        };
+        let base_end_vpn = VirtPageNum(base_vpn.0 + (end_vpn.0 - start_vpn.0));
+
+        let mut flags = PTEFlags::empty();
         if port & 0b0000_0001 != 0 {
             flags |= PTEFlags::R;
         }
-
         if port & 0b0000_0010 != 0 {
             flags |= PTEFlags::W;
         }
-
         if port & 0b0000_0100 != 0 {
             flags |= PTEFlags::X;
         }
-
         flags |= PTEFlags::U;
         flags |= PTEFlags::V;
 
-        while vpn != end_vpn {
-            if let Some(pte) = self.page_table.translate(vpn) {
-                debug!("find vpn {:?} pte flag = {:?}", vpn, pte.flags());
-                if pte.is_valid() {
-                    debug!("map on already mapped vpn {:?}", vpn);
-                    return -1;
-                }
+        self.lazy_mappings.push(LazyMapping {
+            vpn_range: VPNRange::new(base_vpn, base_end_vpn),
+            flags,
+            cow: false,
+        });
+        usize::from(VirtAddr::from(base_vpn)) as isize
+    }
+
+    /// Find the lowest gap of `len` bytes among `areas` and the pending
+    /// mmap regions that starts at or above `MMAP_MIN_ADDR` (or `hint`, if
+    /// given and not below it) and ends at or below the user address space
+    /// ceiling (the region reserved for `TRAP_CONTEXT`/the trampoline).
+    pub fn find_free_area(&self, hint: Option<VirtAddr>, len: usize) -> Option<VirtAddr> {
+        let len_pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let min_vpn = VirtAddr::from(MMAP_MIN_ADDR).ceil();
+        let ceiling_vpn = VirtAddr::from(TRAP_CONTEXT_BASE).floor();
+
+        let mut occupied: Vec<(VirtPageNum, VirtPageNum)> = self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .chain(
+                self.lazy_mappings
+                    .iter()
+                    .map(|m| (m.vpn_range.get_start(), m.vpn_range.get_end())),
+            )
+            .collect();
+        occupied.sort_by_key(|(start, _)| start.0);
+
+        let mut cursor = hint
+            .map(|va| va.floor())
+            .filter(|vpn| vpn.0 >= min_vpn.0)
+            .unwrap_or(min_vpn);
+        for (start, end) in occupied {
+            if end.0 <= cursor.0 {
+                continue;
             }
-            if let Some(frame) = frame_alloc() {
-                let ppn = frame.ppn;
-                debug!(" map vpn {:?} and ppn {:?} flag {:?}", vpn, ppn, flags);
-                self.page_table.map(vpn, ppn, flags);
-                self.mmap_frames.insert(vpn, frame);
-            } else {
-                return -1;
+            if cursor.0 + len_pages <= start.0 {
+                return Some(cursor.into());
             }
-            vpn.step();
+            cursor = end;
+        }
+        if cursor.0 + len_pages <= ceiling_vpn.0 {
+            Some(cursor.into())
+        } else {
+            None
         }
+    }
 
-        0
+    /// Handle a page fault at `fault_vpn` that falls inside a pending lazy
+    /// mmap region: allocate and zero a frame, install the PTE with the
+    /// flags recorded by `mmap`, and remember it in `mmap_frames`. Returns
+    /// `false` if no lazy mapping covers the address.
+    ///
+    /// Called from the `PageFault` arm of the trap handler in `trap`,
+    /// alongside [`MemorySet::handle_cow_fault`]; not part of this module.
+    pub fn handle_lazy_page_fault(&mut self, fault_vpn: VirtPageNum) -> bool {
+        let flags = match self
+            .lazy_mappings
+            .iter()
+            .find(|m| m.vpn_range.get_start().0 <= fault_vpn.0 && fault_vpn.0 < m.vpn_range.get_end().0)
+        {
+            Some(m) => m.flags,
+            None => return false,
+        };
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let ppn = frame.ppn;
+        ppn.get_bytes_array().fill(0);
+        self.page_table.map(fault_vpn, ppn, flags);
+        self.mmap_frames.insert(fault_vpn, Arc::new(frame));
+        true
     }
 
     /// mmunmap
+    ///
+    /// `[start_vpn, end_vpn)` may be a sub-range of a larger mmap'd region
+    /// (or span several of them); each overlapping `LazyMapping` is split,
+    /// keeping whichever leftover piece(s) fall outside the unmapped range.
+    /// Returns -1 only if the range doesn't overlap any mmap region that is
+    /// still mapped.
     pub fn munmap(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> isize {
+        let mut touched = false;
+        let mut kept = Vec::new();
+        for mapping in self.lazy_mappings.drain(..) {
+            let m_start = mapping.vpn_range.get_start();
+            let m_end = mapping.vpn_range.get_end();
+            if m_end.0 <= start_vpn.0 || end_vpn.0 <= m_start.0 {
+                // no overlap with the range being unmapped
+                kept.push(mapping);
+                continue;
+            }
+            touched = true;
+            if m_start.0 < start_vpn.0 {
+                kept.push(LazyMapping {
+                    vpn_range: VPNRange::new(m_start, start_vpn),
+                    flags: mapping.flags,
+                    cow: mapping.cow,
+                });
+            }
+            if end_vpn.0 < m_end.0 {
+                kept.push(LazyMapping {
+                    vpn_range: VPNRange::new(end_vpn, m_end),
+                    flags: mapping.flags,
+                    cow: mapping.cow,
+                });
+            }
+        }
+        if !touched {
+            debug!("munmap on a range that was never mmap'd");
+            return -1;
+        }
+        self.lazy_mappings = kept;
+
         let mut vpn = start_vpn;
         while vpn != end_vpn {
-            if let Some(pte) = self.page_table.translate(vpn) {
-                if !pte.is_valid() {
-                    debug!("unmap on no map vpn");
-                    return -1;
-                }
-            } else {
-                return -1;
+            if self.mmap_frames.remove(&vpn).is_some() {
+                self.page_table.unmap(vpn);
             }
-            self.page_table.unmap(vpn);
-            self.mmap_frames.remove(&vpn);
             vpn.step();
         }
         0
     }
+
+    /// Resize the mmap region starting at `old_start`, mirroring the
+    /// `shrink_to`/`append_to` resize primitives `MapArea` already exposes.
+    /// Growing in place extends the region if the pages right after it are
+    /// free; otherwise, if `may_move` is set, the whole region (including
+    /// any frames already faulted in) is relocated via
+    /// [`MemorySet::find_free_area`]. Returns the (possibly new) base
+    /// address, or -1 on overlap/allocation failure.
+    ///
+    /// Unlike [`MemorySet::munmap`], this does not split a mapping:
+    /// `old_start`/`old_len` must refer to an entire `mmap`'d region
+    /// exactly (one previously returned by `mmap`, or by an earlier
+    /// `mremap`/`munmap` of the same region). A sub-range of a larger
+    /// region is rejected with -1 rather than being carved out of it,
+    /// since there's no single sensible new base address for "resize part
+    /// of a mapping".
+    pub fn mremap(
+        &mut self,
+        old_start: VirtPageNum,
+        old_len: usize,
+        new_len: usize,
+        may_move: bool,
+    ) -> isize {
+        let old_pages = (old_len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let new_pages = (new_len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let old_end = VirtPageNum(old_start.0 + old_pages);
+        let new_end = VirtPageNum(old_start.0 + new_pages);
+
+        let idx = match self
+            .lazy_mappings
+            .iter()
+            .position(|m| m.vpn_range.get_start() == old_start && m.vpn_range.get_end() == old_end)
+        {
+            Some(idx) => idx,
+            None => {
+                debug!("mremap on a range that was never mmap'd");
+                return -1;
+            }
+        };
+
+        if new_pages <= old_pages {
+            // shrink: drop any frames already faulted into the freed tail
+            let mut vpn = new_end;
+            while vpn.0 != old_end.0 {
+                if self.mmap_frames.remove(&vpn).is_some() {
+                    self.page_table.unmap(vpn);
+                }
+                vpn.step();
+            }
+            self.lazy_mappings[idx].vpn_range = VPNRange::new(old_start, new_end);
+            return usize::from(VirtAddr::from(old_start)) as isize;
+        }
+
+        if !self.range_overlaps(old_end, new_end) {
+            // grow in place: the tail is still a pending lazy VMA
+            self.lazy_mappings[idx].vpn_range = VPNRange::new(old_start, new_end);
+            return usize::from(VirtAddr::from(old_start)) as isize;
+        }
+
+        if !may_move {
+            return -1;
+        }
+        let new_base = match self.find_free_area(None, new_len) {
+            Some(va) => va.floor(),
+            None => return -1,
+        };
+        // move every frame already faulted in to the new range, re-establishing
+        // identical PTE flags; untouched pages stay pending and lazily fault in later
+        let mut vpn = old_start;
+        while vpn.0 != old_end.0 {
+            if let Some(frame) = self.mmap_frames.remove(&vpn) {
+                let pte_flags = self.page_table.translate(vpn).unwrap().flags();
+                self.page_table.unmap(vpn);
+                let new_vpn = VirtPageNum(new_base.0 + (vpn.0 - old_start.0));
+                self.page_table.map(new_vpn, frame.ppn, pte_flags);
+                self.mmap_frames.insert(new_vpn, frame);
+            }
+            vpn.step();
+        }
+        let new_vpn_end = VirtPageNum(new_base.0 + new_pages);
+        self.lazy_mappings[idx].vpn_range = VPNRange::new(new_base, new_vpn_end);
+        usize::from(VirtAddr::from(new_base)) as isize
+    }
+
+    /// Whether `[start_vpn, end_vpn)` overlaps any existing `MapArea` or
+    /// pending lazy mmap region.
+    fn range_overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let overlaps = |a_start: VirtPageNum, a_end: VirtPageNum| {
+            a_start.0 < end_vpn.0 && start_vpn.0 < a_end.0
+        };
+        self.areas
+            .iter()
+            .any(|area| overlaps(area.vpn_range.get_start(), area.vpn_range.get_end()))
+            || self
+                .lazy_mappings
+                .iter()
+                .any(|m| overlaps(m.vpn_range.get_start(), m.vpn_range.get_end()))
+    }
+}
+
+/// A pending mmap region that has been validated but not yet backed by
+/// physical frames; pages are faulted in lazily on first access.
+struct LazyMapping {
+    vpn_range: VPNRange,
+    flags: PTEFlags,
+    /// Whether frames already faulted into this region are shared
+    /// copy-on-write with another address space (set when `flags` grants
+    /// `W` and the region survives a fork); see
+    /// [`MemorySet::from_existed_user`] and
+    /// [`MemorySet::handle_cow_fault`].
+    cow: bool,
 }
 /// map area structure, controls a contiguous piece of virtual memory
 /// 逻辑段的结构与方法
 pub struct MapArea {
     vpn_range: VPNRange, // 迭代器，元素为所有的虚拟页
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>, //记录映射关系
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>, //记录映射关系，Arc使得fork出的子进程可以共享同一物理页帧
     map_type: MapType,
     map_perm: MapPermission, // 逻辑段的访问权限
+    /// Whether this area's pages are currently copy-on-write (shared with
+    /// another address space, mapped without `W` even though `map_perm`
+    /// grants it). Set by [`MemorySet::from_existed_user`].
+    cow: bool,
 }
 
 impl MapArea {
@@ -364,6 +771,7 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            cow: false,
         }
     }
     ////为MapArea中的一个虚拟页映射物理页，若为恒等映射，则直接映射到vpn同号的ppn，否则
@@ -371,18 +779,60 @@ impl MapArea {
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
-            MapType::Identical => { //恒等映射
+            MapType::Identical | MapType::IdenticalHuge => { //恒等映射
                 ppn = PhysPageNum(vpn.0);
             }
-            MapType::Framed => { 
+            MapType::Framed => {
                 let frame = frame_alloc().unwrap();
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
     }
+    /// Map `vpn` to an already-allocated frame shared with another `MapArea`
+    /// (used by [`MemorySet::from_existed_user`] to set up copy-on-write).
+    fn map_shared(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+        pte_flags: PTEFlags,
+    ) {
+        let ppn = frame.ppn;
+        self.data_frames.insert(vpn, frame);
+        page_table.map(vpn, ppn, pte_flags);
+    }
+    /// Resolve a store page fault on a copy-on-write page of this area.
+    /// If the underlying frame is still shared, a fresh copy is made and
+    /// remapped with `W` restored; if this side already holds the only
+    /// reference, `W` is simply restored in place.
+    fn cow_remap(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.cow {
+            return false;
+        }
+        let shared = match self.data_frames.get(&vpn) {
+            Some(frame) => Arc::strong_count(frame) > 1,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.unmap(vpn);
+        if shared {
+            let old_ppn = self.data_frames.get(&vpn).unwrap().ppn;
+            let new_frame = frame_alloc().unwrap();
+            let new_ppn = new_frame.ppn;
+            new_ppn
+                .get_bytes_array()
+                .copy_from_slice(old_ppn.get_bytes_array());
+            page_table.map(vpn, new_ppn, pte_flags);
+            self.data_frames.insert(vpn, Arc::new(new_frame));
+        } else {
+            let ppn = self.data_frames.get(&vpn).unwrap().ppn;
+            page_table.map(vpn, ppn, pte_flags);
+        }
+        true
+    }
     //解除映射
     #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
@@ -393,10 +843,31 @@ impl MapArea {
     }
     //完成对MapArea中所有虚拟页的映射
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.map_type == MapType::IdenticalHuge {
+            self.map_identical_huge(page_table);
+            return;
+        }
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
+    /// Map a naturally-2MiB-aligned identity region using a single Sv39
+    /// megapage (level-1) PTE per 2 MiB span, falling back to ordinary
+    /// 4 KiB pages for any unaligned head/tail of the area.
+    fn map_identical_huge(&mut self, page_table: &mut PageTable) {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        let end = self.vpn_range.get_end();
+        let mut vpn = self.vpn_range.get_start();
+        while vpn.0 != end.0 {
+            if vpn.0 % HUGE_PAGE_VPN_COUNT == 0 && vpn.0 + HUGE_PAGE_VPN_COUNT <= end.0 {
+                page_table.map_huge(vpn, PhysPageNum(vpn.0), pte_flags);
+                vpn = VirtPageNum(vpn.0 + HUGE_PAGE_VPN_COUNT);
+            } else {
+                self.map_one(page_table, vpn);
+                vpn.step();
+            }
+        }
+    }
     //解除对MapArea中所有虚拟页的映射
     #[allow(unused)]
     pub fn unmap(&mut self, page_table: &mut PageTable) {
@@ -445,10 +916,18 @@ impl MapArea {
     }
 }
 
+/// Number of 4 KiB VPNs covered by one Sv39 2 MiB megapage (a level-1 leaf
+/// PTE maps 512 level-0 entries' worth of address space at once).
+const HUGE_PAGE_VPN_COUNT: usize = 512;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
-pub enum MapType {  
+/// map type for memory set: identical, huge-page identical, or framed
+pub enum MapType {
     Identical,  //表示对等映射
+    /// Identity mapping installed as Sv39 2 MiB megapages wherever the area
+    /// is naturally aligned, instead of one leaf PTE per 4 KiB page; see
+    /// [`MapArea::map_identical_huge`] and `PageTable::map_huge`.
+    IdenticalHuge,
     Framed,  //表示对于每个虚拟页面都需要映射到一个新分配的物理页帧
 }
 
@@ -0,0 +1,209 @@
+//! Implementation of [`PageTableEntry`] and [`PageTable`].
+
+use super::{frame_alloc, FrameTracker};
+use super::{PhysPageNum, StepByOne, VirtPageNum};
+use alloc::vec;
+use alloc::vec::Vec;
+
+bitflags! {
+    /// page table entry flags
+    pub struct PTEFlags: u8 {
+        const V = 1 << 0;
+        const R = 1 << 1;
+        const W = 1 << 2;
+        const X = 1 << 3;
+        const U = 1 << 4;
+        const G = 1 << 5;
+        const A = 1 << 6;
+        const D = 1 << 7;
+    }
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+/// page table entry structure
+pub struct PageTableEntry {
+    pub bits: usize,
+}
+
+impl PageTableEntry {
+    /// Build a PTE pointing at `ppn` with `flags` set.
+    pub fn new(ppn: PhysPageNum, flags: PTEFlags) -> Self {
+        PageTableEntry {
+            bits: ppn.0 << 10 | flags.bits as usize,
+        }
+    }
+    /// An all-zero, invalid PTE.
+    pub fn empty() -> Self {
+        PageTableEntry { bits: 0 }
+    }
+    /// The physical page number this entry points at. For an ordinary
+    /// 4 KiB leaf or a next-level table pointer this is exact; for a
+    /// megapage/gigapage leaf this is the *aligned span base*, not
+    /// necessarily the frame backing a particular VPN inside that span —
+    /// use [`PageTable::translate`] to get the correct per-VPN PPN for a
+    /// huge page.
+    pub fn ppn(&self) -> PhysPageNum {
+        (self.bits >> 10 & ((1usize << 44) - 1)).into()
+    }
+    pub fn flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.bits as u8).unwrap()
+    }
+    pub fn is_valid(&self) -> bool {
+        (self.flags() & PTEFlags::V) != PTEFlags::empty()
+    }
+    pub fn readable(&self) -> bool {
+        (self.flags() & PTEFlags::R) != PTEFlags::empty()
+    }
+    pub fn writable(&self) -> bool {
+        (self.flags() & PTEFlags::W) != PTEFlags::empty()
+    }
+    pub fn executable(&self) -> bool {
+        (self.flags() & PTEFlags::X) != PTEFlags::empty()
+    }
+    /// Whether this entry is a Sv39 leaf rather than a pointer to the
+    /// next-level table: valid with at least one of `R`/`W`/`X` set. The
+    /// entry's level is not checked; above level 0 such a leaf is a
+    /// megapage/gigapage, and `PageTable::find_pte` stops descending as
+    /// soon as it sees one.
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid() && (self.readable() || self.writable() || self.executable())
+    }
+}
+
+/// page table structure
+pub struct PageTable {
+    root_ppn: PhysPageNum,
+    frames: Vec<FrameTracker>,
+}
+
+impl PageTable {
+    pub fn new() -> Self {
+        let frame = frame_alloc().unwrap();
+        PageTable {
+            root_ppn: frame.ppn,
+            frames: vec![frame],
+        }
+    }
+    /// Temporarily used to get arguments from user space.
+    pub fn from_token(satp: usize) -> Self {
+        Self {
+            root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
+            frames: Vec::new(),
+        }
+    }
+    /// Walk down to the level-0 PTE for `vpn`, allocating intermediate
+    /// tables as needed. Used by `map` and `unmap`; megapages are installed
+    /// directly via `map_huge` instead, which stops at level 1.
+    ///
+    /// Panics if `vpn` falls inside an already-installed megapage/gigapage:
+    /// descending further would treat that leaf's data-frame PPN as a
+    /// next-level table pointer and corrupt it.
+    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+        let idxs = vpn.indexes();
+        let mut ppn = self.root_ppn;
+        let mut result: Option<&mut PageTableEntry> = None;
+        for (i, idx) in idxs.iter().enumerate() {
+            let pte = &mut ppn.get_pte_array()[*idx];
+            if i == 2 {
+                result = Some(pte);
+                break;
+            }
+            if pte.is_valid() {
+                assert!(
+                    !pte.is_leaf(),
+                    "vpn {:?} falls inside a huge page mapped at level {}; \
+                     map/unmap can't partially remap it",
+                    vpn,
+                    2 - i
+                );
+            } else {
+                let frame = frame_alloc().unwrap();
+                *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+                self.frames.push(frame);
+            }
+            ppn = pte.ppn();
+            result = Some(pte);
+        }
+        result
+    }
+    /// Walk down to the PTE for `vpn`, stopping early (and returning that
+    /// entry) if a megapage/gigapage leaf is encountered before level 0.
+    /// Returns `None` if any table along the way is not yet valid.
+    ///
+    /// Alongside the entry, returns the Sv39 level it was found at (0 for
+    /// an ordinary 4 KiB leaf, 1 for a 2 MiB megapage, 2 for a 1 GiB
+    /// gigapage) so `translate` can reconstruct the correct per-VPN PPN
+    /// for a huge leaf.
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<(usize, &PageTableEntry)> {
+        let idxs = vpn.indexes();
+        let mut ppn = self.root_ppn;
+        let mut result = None;
+        for (i, idx) in idxs.iter().enumerate() {
+            let pte = &ppn.get_pte_array()[*idx];
+            if !pte.is_valid() {
+                return None;
+            }
+            if pte.is_leaf() || i == 2 {
+                result = Some((2 - i, pte));
+                break;
+            }
+            ppn = pte.ppn();
+        }
+        result
+    }
+    /// Map a single 4 KiB page. Panics if `vpn` is already mapped.
+    #[allow(unused)]
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+    /// Install a 2 MiB Sv39 megapage by writing the level-1 PTE's `R`/`W`/`X`
+    /// bits directly instead of descending to a level-0 leaf table. `vpn`
+    /// and `ppn` must both be 2 MiB-aligned (their low 9 bits zero). Panics
+    /// if the level-1 entry is already valid.
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        assert_eq!(vpn.0 & 0x1ff, 0, "map_huge requires a 2MiB-aligned vpn");
+        assert_eq!(ppn.0 & 0x1ff, 0, "map_huge requires a 2MiB-aligned ppn");
+        let idxs = vpn.indexes();
+        let mut cur_ppn = self.root_ppn;
+        // descend through the root (level 2) table only; level 1 is where
+        // the megapage leaf lives
+        let l1_pte = &mut cur_ppn.get_pte_array()[idxs[0]];
+        if !l1_pte.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *l1_pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        let l1_table_ppn = l1_pte.ppn();
+        let pte = &mut l1_table_ppn.get_pte_array()[idxs[1]];
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+    /// Unmap a single 4 KiB page.
+    #[allow(unused)]
+    pub fn unmap(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        *pte = PageTableEntry::empty();
+    }
+    /// Translate `vpn` to a PTE holding the actual frame that backs it. For
+    /// an ordinary 4 KiB leaf this is just the stored entry; for a
+    /// megapage/gigapage leaf, `ppn()` only stores the aligned span base,
+    /// so the low bits of `vpn` within that span are spliced in to recover
+    /// the frame this specific `vpn` maps to.
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        let (level, pte) = self.find_pte(vpn)?;
+        if level == 0 {
+            return Some(*pte);
+        }
+        let span_pages = 1usize << (9 * level);
+        let offset = vpn.0 & (span_pages - 1);
+        let ppn = PhysPageNum(pte.ppn().0 | offset);
+        Some(PageTableEntry::new(ppn, pte.flags()))
+    }
+    pub fn token(&self) -> usize {
+        8usize << 60 | self.root_ppn.0
+    }
+}